@@ -24,15 +24,11 @@ fn obfuscate(tokens: TokenStream) -> Result<TokenStream, syn::Error> {
 
 fn obfuscate_str(lit: &syn::LitStr) -> TokenStream {
 	let value = lit.value();
-	let mut obfuscated = Vec::with_capacity(value.len());
-	for byte in value.as_bytes() {
-		let pad: u8 = rand::random();
-		obfuscated.push(u16::from_le_bytes([byte ^ pad, pad]));
-	}
+	let obfuscated = obfuscate_bytes(value.as_bytes());
 
 	quote! {
 		{
-			const DATA: &[u16] = &[#(#obfuscated,)*];
+			const DATA: &[u8] = &[#(#obfuscated,)*];
 			(::core::marker::PhantomData::<str>, DATA)
 		}
 	}
@@ -40,15 +36,11 @@ fn obfuscate_str(lit: &syn::LitStr) -> TokenStream {
 
 fn obfuscate_byte_str(lit: &syn::LitByteStr) -> TokenStream {
 	let value = lit.value();
-	let mut obfuscated = Vec::with_capacity(value.len());
-	for byte in value {
-		let pad: u8 = rand::random();
-		obfuscated.push(u16::from_le_bytes([byte ^ pad, pad]));
-	}
+	let obfuscated = obfuscate_bytes(&value);
 
 	quote! {
 		{
-			const DATA: &[u16] = &[#(#obfuscated,)*];
+			const DATA: &[u8] = &[#(#obfuscated,)*];
 			(::core::marker::PhantomData::<[u8]>, DATA)
 		}
 	}
@@ -56,20 +48,44 @@ fn obfuscate_byte_str(lit: &syn::LitByteStr) -> TokenStream {
 
 fn obfuscate_cstr(lit: &syn::LitCStr) -> TokenStream {
 	let value = lit.value();
-	let mut obfuscated = Vec::with_capacity(value.as_bytes_with_nul().len());
-	for byte in value.as_bytes_with_nul() {
-		let pad: u8 = rand::random();
-		obfuscated.push(u16::from_le_bytes([byte ^ pad, pad]));
-	}
+	let obfuscated = obfuscate_bytes(value.as_bytes_with_nul());
 
 	quote! {
 		{
-			const DATA: &[u16] = &[#(#obfuscated,)*];
+			const DATA: &[u8] = &[#(#obfuscated,)*];
 			(::core::marker::PhantomData::<::core::ffi::CStr>, DATA)
 		}
 	}
 }
 
+/// Encrypt `value` with a keystream derived from a freshly generated seed.
+///
+/// The seed is prepended to the returned buffer as 8 little-endian bytes,
+/// followed by one ciphertext byte per input byte.
+fn obfuscate_bytes(value: &[u8]) -> Vec<u8> {
+	let seed: u64 = rand::random();
+	let mut state = seed;
+	let mut obfuscated = Vec::with_capacity(8 + value.len());
+	obfuscated.extend_from_slice(&seed.to_le_bytes());
+	for &byte in value {
+		obfuscated.push(byte ^ splitmix64_next(&mut state));
+	}
+	obfuscated
+}
+
+/// Advance a splitmix64 generator and return one keystream byte.
+///
+/// This must stay in sync with the copy in `obfustr::splitmix64_next`,
+/// which regenerates the same keystream to decrypt the data produced here.
+fn splitmix64_next(state: &mut u64) -> u8 {
+	*state = state.wrapping_add(0x9E3779B97F4A7C15);
+	let mut z = *state;
+	z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+	z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+	z ^= z >> 31;
+	z as u8
+}
+
 fn expect_literal(tokens: &mut proc_macro2::token_stream::IntoIter) -> Result<syn::Lit, syn::Error> {
 	let tree = tokens.next()
 		.ok_or_else(|| syn::Error::new(Span::call_site(), "unexpected end of arguments, expected a literal"))?;
@@ -0,0 +1,94 @@
+//! A secret that stays encrypted for as long as it is held, not just until first use.
+
+use core::marker::PhantomData;
+use std::sync::OnceLock;
+
+use crate::{allocate_buffer, splitmix64_next, Buffer, Data, Decrypted, HeapAlloc, Obfuscated};
+
+/// A long-lived secret, built from an [`obfustr::encrypt!()`](crate::encrypt!) literal.
+///
+/// Where [`crate::obfuscate!()`] decrypts once into a temporary, `Encrypted<T>` is meant to be
+/// kept around for the life of the program (a token, a password, ...). It stores its payload
+/// re-encrypted under a session key that is generated once per process and never appears in the
+/// binary, and only decrypts it into a short-lived scratch buffer for the duration of a
+/// [`map()`](Self::map) call. Between calls, and even before the first call, the stored form is
+/// always encrypted.
+pub struct Encrypted<T: ?Sized + 'static> {
+	marker: PhantomData<T>,
+	source: &'static Obfuscated<T>,
+	cache: OnceLock<Buffer>,
+}
+
+impl<T: ?Sized + 'static> Encrypted<T> {
+	/// Wrap an already-obfuscated static value.
+	///
+	/// You should normally use [`crate::encrypt!()`] instead of calling this directly.
+	#[doc(hidden)]
+	pub const fn new(source: &'static Obfuscated<T>) -> Self {
+		Self {
+			marker: PhantomData,
+			source,
+			cache: OnceLock::new(),
+		}
+	}
+}
+
+impl<T: ?Sized + Data + 'static> Encrypted<T> {
+	/// Decrypt the secret, run `f` with it, then erase the decrypted scratch buffer.
+	///
+	/// The first call re-encrypts the payload under the process-wide session key
+	/// (generating that key if this is the first `Encrypted` value to be used) and caches the
+	/// result; the original compile-time obfuscated form is not touched again afterwards.
+	pub fn map<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+		let mut key = session_key();
+		let cache = self.cache.get_or_init(|| self.source.reencrypt(key));
+
+		let mut state = key;
+		let mut data = allocate_buffer(cache.as_ref().len());
+		for (i, byte) in data.as_mut().iter_mut().enumerate() {
+			// SAFETY: we read from a pointer directly created from a reference, so the pointer must be valid.
+			let elem = unsafe { core::ptr::read_volatile(&cache.as_ref()[i]) };
+			*byte = elem ^ splitmix64_next(&mut state);
+		}
+
+		// SAFETY: `state` and `key` are plain locals we own; overwriting them is always valid.
+		unsafe {
+			core::ptr::write_volatile(&mut state, 0);
+			core::ptr::write_volatile(&mut key, 0);
+		}
+
+		let plain: Decrypted<T, HeapAlloc> = Decrypted { marker: PhantomData, data };
+		f(plain.as_inner())
+	}
+}
+
+/// The per-process key used to re-encrypt [`Encrypted<T>`] payloads.
+///
+/// Generated on first use and kept in the same kind of buffer as decrypted data, so with the
+/// `mlock` feature enabled it is locked in memory just like any other secret.
+static SESSION_KEY: OnceLock<Buffer> = OnceLock::new();
+
+fn session_key() -> u64 {
+	let buffer = SESSION_KEY.get_or_init(|| {
+		let seed: u64 = rand::random();
+		let mut buffer = allocate_buffer(8);
+		buffer.as_mut().copy_from_slice(&seed.to_le_bytes());
+		buffer
+	});
+
+	let mut seed = [0u8; 8];
+	for (i, byte) in seed.iter_mut().enumerate() {
+		// SAFETY: we read from a pointer directly created from a reference, so the pointer must be valid.
+		*byte = unsafe { core::ptr::read_volatile(&buffer.as_ref()[i]) };
+	}
+	let key = u64::from_le_bytes(seed);
+
+	for byte in seed.iter_mut() {
+		// SAFETY: `seed` is a plain local we own; overwriting it is always valid.
+		unsafe {
+			core::ptr::write_volatile(byte, 0);
+		}
+	}
+
+	key
+}
@@ -2,16 +2,26 @@
 //!
 //! May be useful to slightly hinder people from reverse engineering your program.
 //!
-//! The obfuscated strings are encrypted by XORing with a random bit pattern,
-//! and the random bit pattern is stored next to the encrypted data.
-//! This means you can not easily find the string in the binary by inspecting it with conventional tools.
+//! The obfuscated strings are encrypted by XORing with a keystream derived from a random seed,
+//! using a splitmix64 generator re-seeded at decryption time.
+//! This means you can not easily find the string in the binary by inspecting it with conventional tools,
+//! and the keystream is not sitting in the binary next to the ciphertext as a simple pad would be.
 //!
-//! However, the decryption key is stored directly next to the data,
-//! so this does not effectively protect data that needs to be kept secret.
+//! However, the seed is still stored next to the data,
+//! so this does not effectively protect data that needs to be kept secret
+//! from an attacker willing to run the generator themselves.
 //!
 //! The library supports obfuscating string literals, byte string literals and C string literals.
 //! All of them are processed using the [`obfuscate!()`] macro.
 //!
+//! # `no_std` support
+//!
+//! This crate is `no_std`. [`Obfuscated::ct_eq()`] and the `PartialEq` impls never allocate, so
+//! they work with no allocator at all. [`Obfuscated::decrypt()`] needs somewhere to put the
+//! plaintext: enable the `alloc` feature to decrypt onto the heap with [`obfuscate!()`], or
+//! implement [`SecureAlloc`] yourself (for a fixed static arena, for example) and call
+//! `obfuscated.decrypt::<YourAlloc>()` directly.
+//!
 //! # Example 1: Obfuscate a string literal
 //!```
 //! # use obfustr::obfuscate;
@@ -29,9 +39,32 @@
 //! # use obfustr::obfuscate;
 //! let message = obfuscate!(c"Hello world!"); // This gives a `CStr`.
 //!```
+//!
+//! # Example 4: Keep a secret encrypted for the life of the program
+//!```
+//! # use obfustr::encrypt;
+//! let password = encrypt!("Hello world!");
+//! password.map(|password| println!("{password}"));
+//!```
+
+#![no_std]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "std")]
+extern crate std;
 
 use core::marker::PhantomData;
 
+#[cfg(feature = "mlock")]
+mod secure;
+
+#[cfg(feature = "std")]
+mod encrypted;
+#[cfg(feature = "std")]
+pub use encrypted::Encrypted;
+
 #[doc(hidden)]
 pub use obfustr_macros as macros__;
 
@@ -42,6 +75,9 @@ pub use obfustr_macros as macros__;
 /// It gives you a reference to a temporary that holds the de-obfucated string.
 /// The memory holding the de-obfuscated data will be overwritten with zeroes when the value is dropped.
 ///
+/// Requires the `alloc` feature, to provide somewhere to decrypt into.
+/// Without it, use [`obfuscate_obj!()`] and [`Obfuscated::decrypt()`] with your own [`SecureAlloc`].
+///
 /// # Usage
 /// ```
 /// # use obfustr::obfuscate;
@@ -52,31 +88,146 @@ pub use obfustr_macros as macros__;
 /// std::io::stdout().write_all(obfuscate!(c"Hello world!").to_bytes())?;
 /// # Ok(())
 /// # }
+#[cfg(feature = "alloc")]
 #[macro_export]
 macro_rules! obfuscate {
+	($($tokens:tt)*) => {
+		$crate::obfuscate_obj!($($tokens)*).decrypt::<$crate::HeapAlloc>().as_inner()
+	};
+}
+
+/// Obfuscate a string literal, without decrypting it.
+///
+/// This gives you a reference to the still-encrypted [`Obfuscated`] value.
+/// Mainly useful to compare the obfuscated data against a candidate value
+/// without ever decrypting the full value, using [`Obfuscated`]'s `PartialEq` impls
+/// or its [`Obfuscated::ct_eq()`] method, or to call [`Obfuscated::decrypt()`]
+/// with your own [`SecureAlloc`] implementation.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! obfuscate_obj {
 	($($tokens:tt)*) => {
 		const {
 			let (marker, data) = $crate::macros__::obfuscate_raw!($($tokens)*);
 			unsafe { $crate::Obfuscated::new_unchecked(marker, data) }
-		}.decrypt().as_inner()
+		}
+	};
+}
+
+/// Obfuscate a string literal into a long-lived [`Encrypted`] secret.
+///
+/// Unlike [`obfuscate!()`], the result is not decrypted immediately.
+/// Instead it can be kept around for as long as needed and only decrypts
+/// into a short-lived scratch buffer for the duration of [`Encrypted::map()`].
+///
+/// Requires the `std` feature.
+///
+/// # Usage
+/// ```
+/// # use obfustr::encrypt;
+/// let secret = encrypt!("Hello world!");
+/// secret.map(|message| println!("{message}"));
+/// ```
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! encrypt {
+	($($tokens:tt)*) => {
+		$crate::Encrypted::new($crate::obfuscate_obj!($($tokens)*))
 	};
 }
 
 /// A slice of obfuscated string data.
+///
+/// The first 8 bytes are the little-endian splitmix64 seed, the rest is the ciphertext.
 #[repr(transparent)]
 #[doc(hidden)]
 pub struct Obfuscated<T: ?Sized> {
 	marker: PhantomData<T>,
-	data: [u16],
+	data: [u8],
 }
 
 /// A decrypted string, which owns its data.
 ///
 /// Overwrites the data with zeroes when dropped.
+///
+/// Generic over the [`SecureAlloc`] that supplied the storage for the plaintext bytes.
 #[doc(hidden)]
-pub struct Decrypted<T: ?Sized> {
-	marker: PhantomData<T>,
-	data: Box<[u8]>,
+pub struct Decrypted<T: ?Sized, A: SecureAlloc> {
+	pub(crate) marker: PhantomData<T>,
+	pub(crate) data: A::Buffer,
+}
+
+/// A source of storage for the plaintext produced by [`Obfuscated::decrypt()`].
+///
+/// Implement this to supply your own backing store for decrypted secrets, for example a fixed
+/// static arena on a platform without a heap. The `alloc` feature provides [`HeapAlloc`], which
+/// allocates on the heap (or, with the `mlock` feature enabled, in a locked memory mapping).
+pub trait SecureAlloc {
+	/// Owned storage for exactly the requested number of bytes.
+	type Buffer: AsRef<[u8]> + AsMut<[u8]>;
+
+	/// Allocate zeroed storage for `len` bytes.
+	fn allocate(len: usize) -> Self::Buffer;
+}
+
+/// The default [`SecureAlloc`]: a heap allocation, or (with the `mlock` feature) a locked mapping.
+#[cfg(feature = "alloc")]
+pub struct HeapAlloc;
+
+#[cfg(feature = "alloc")]
+impl SecureAlloc for HeapAlloc {
+	type Buffer = Buffer;
+
+	fn allocate(len: usize) -> Buffer {
+		allocate_buffer(len)
+	}
+}
+
+/// Storage allocated by [`HeapAlloc`].
+///
+/// With the `mlock` feature enabled, [`allocate_buffer`] prefers a [`secure::LockedBuffer`]
+/// and only falls back to a normal heap allocation if locking the memory fails.
+#[cfg(feature = "alloc")]
+pub enum Buffer {
+	Heap(alloc::boxed::Box<[u8]>),
+	#[cfg(feature = "mlock")]
+	Locked(secure::LockedBuffer),
+}
+
+#[cfg(feature = "alloc")]
+impl AsRef<[u8]> for Buffer {
+	fn as_ref(&self) -> &[u8] {
+		match self {
+			Self::Heap(data) => data,
+			#[cfg(feature = "mlock")]
+			Self::Locked(data) => data.as_slice(),
+		}
+	}
+}
+
+#[cfg(feature = "alloc")]
+impl AsMut<[u8]> for Buffer {
+	fn as_mut(&mut self) -> &mut [u8] {
+		match self {
+			Self::Heap(data) => data,
+			#[cfg(feature = "mlock")]
+			Self::Locked(data) => data.as_mut_slice(),
+		}
+	}
+}
+
+/// Allocate `len` bytes to hold decrypted data.
+///
+/// With the `mlock` feature enabled, this tries to lock the allocation in memory
+/// (and exclude it from core dumps) first, falling back to the heap if that fails.
+#[cfg(feature = "alloc")]
+pub(crate) fn allocate_buffer(len: usize) -> Buffer {
+	#[cfg(feature = "mlock")]
+	if let Some(locked) = secure::LockedBuffer::new(len) {
+		return Buffer::Locked(locked);
+	}
+
+	Buffer::Heap(alloc::vec![0u8; len].into_boxed_slice())
 }
 
 impl<T: ?Sized> Obfuscated<T> {
@@ -85,55 +236,156 @@ impl<T: ?Sized> Obfuscated<T> {
 	/// # Safety
 	/// The data must decrypt to valid UTF-8.
 	#[doc(hidden)]
-	pub const unsafe fn new_unchecked(_: PhantomData<T>, data: &[u16]) -> &Self {
+	pub const unsafe fn new_unchecked(_: PhantomData<T>, data: &[u8]) -> &Self {
 		core::mem::transmute(data)
 	}
 
-	/// Decrypt the obfuscated string.
-	pub fn decrypt(&self) -> Decrypted<T> {
-		let mut data = Box::new_uninit_slice(self.data.len());
-		for i in 0..self.data.len() {
-			// Use read_volatile to avoid the compiler from optimizing away the obfuscation.
-			// SAFETY: we read from a pointer directly created from  a reference, so the pointer must be valid.
-			let elem = unsafe { std::ptr::read_volatile(&self.data[i]) };
-			let [a, b] = elem.to_le_bytes();
-			data[i].write(a ^ b);
+	/// Decrypt the obfuscated string using `A` to allocate storage for the plaintext.
+	pub fn decrypt<A: SecureAlloc>(&self) -> Decrypted<T, A> {
+		let mut state = self.seed();
+		let cipher = self.cipher();
+		let mut data = A::allocate(cipher.len());
+		for (i, byte) in data.as_mut().iter_mut().enumerate() {
+			// SAFETY: we read from a pointer directly created from a reference, so the pointer must be valid.
+			let elem = unsafe { core::ptr::read_volatile(&cipher[i]) };
+			*byte = elem ^ splitmix64_next(&mut state);
 		}
 
-		// SAFETY: we just wrote to every single byte in `data`.
-		let data = unsafe { data.assume_init() };
-
 		Decrypted {
 			marker: self.marker,
 			data,
 		}
 	}
+
+	/// Compare the decrypted data against `candidate`, without ever materializing the full plaintext.
+	///
+	/// The comparison always decrypts and compares every byte before returning,
+	/// so it runs in constant time with respect to the position of the first mismatch,
+	/// and a length mismatch is folded into the result rather than returned early.
+	/// The loop always runs `cipher.len()` times, regardless of `candidate`'s length,
+	/// so runtime depends only on the secret's compile-time-fixed length, never on the candidate.
+	/// This never allocates, so it works without the `alloc` feature.
+	pub fn ct_eq(&self, candidate: &[u8]) -> bool {
+		let cipher = self.cipher();
+		let mut diff = (cipher.len() != candidate.len()) as u8;
+
+		let mut state = self.seed();
+		for (i, &cipher_byte) in cipher.iter().enumerate() {
+			// SAFETY: we read from a pointer directly created from a reference, so the pointer must be valid.
+			let elem = unsafe { core::ptr::read_volatile(&cipher_byte) };
+			let plain = elem ^ splitmix64_next(&mut state);
+			let candidate_byte = candidate.get(i).copied().unwrap_or(0);
+			diff |= plain ^ candidate_byte;
+		}
+		diff == 0
+	}
+
+	/// Recover the splitmix64 seed stored in the first 8 bytes of `data`.
+	fn seed(&self) -> u64 {
+		let mut seed = [0u8; 8];
+		for (i, byte) in seed.iter_mut().enumerate() {
+			// SAFETY: we read from a pointer directly created from a reference, so the pointer must be valid.
+			*byte = unsafe { core::ptr::read_volatile(&self.data[i]) };
+		}
+		u64::from_le_bytes(seed)
+	}
+
+	/// The ciphertext bytes that follow the seed in `data`.
+	fn cipher(&self) -> &[u8] {
+		&self.data[8..]
+	}
+
+	/// Decrypt with the compile-time seed and re-encrypt the result with `key`.
+	///
+	/// Used by [`crate::Encrypted`] to move a secret from compile-time obfuscation
+	/// to a buffer keyed by the process-wide session key.
+	#[cfg(feature = "std")]
+	pub(crate) fn reencrypt(&self, mut key: u64) -> Buffer {
+		let mut state = self.seed();
+		let mut key_state = key;
+		let cipher = self.cipher();
+		let mut out = allocate_buffer(cipher.len());
+		for (i, byte) in out.as_mut().iter_mut().enumerate() {
+			// SAFETY: we read from a pointer directly created from a reference, so the pointer must be valid.
+			let elem = unsafe { core::ptr::read_volatile(&cipher[i]) };
+			let plain = elem ^ splitmix64_next(&mut state);
+			*byte = plain ^ splitmix64_next(&mut key_state);
+		}
+
+		// SAFETY: `state`, `key_state` and `key` are plain locals we own; overwriting them is always valid.
+		unsafe {
+			core::ptr::write_volatile(&mut state, 0);
+			core::ptr::write_volatile(&mut key_state, 0);
+			core::ptr::write_volatile(&mut key, 0);
+		}
+
+		out
+	}
+}
+
+impl PartialEq<str> for Obfuscated<str> {
+	fn eq(&self, other: &str) -> bool {
+		self.ct_eq(other.as_bytes())
+	}
+}
+
+impl PartialEq<Obfuscated<str>> for str {
+	fn eq(&self, other: &Obfuscated<str>) -> bool {
+		other.ct_eq(self.as_bytes())
+	}
+}
+
+impl PartialEq<[u8]> for Obfuscated<[u8]> {
+	fn eq(&self, other: &[u8]) -> bool {
+		self.ct_eq(other)
+	}
+}
+
+impl PartialEq<Obfuscated<[u8]>> for [u8] {
+	fn eq(&self, other: &Obfuscated<[u8]>) -> bool {
+		other.ct_eq(self)
+	}
+}
+
+/// Advance a splitmix64 generator and return one keystream byte.
+///
+/// This must stay in sync with the copy in `obfustr_macros`, which generates the
+/// keystream used to encrypt the data that this function decrypts.
+pub(crate) fn splitmix64_next(state: &mut u64) -> u8 {
+	*state = state.wrapping_add(0x9E3779B97F4A7C15);
+	let mut z = *state;
+	z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+	z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+	z ^= z >> 31;
+	z as u8
 }
 
-impl<T: ?Sized + Data> Decrypted<T> {
+impl<T: ?Sized + Data, A: SecureAlloc> Decrypted<T, A> {
 	/// Get the decrypted data.
 	pub fn as_inner(&self) -> &T {
 		// SAFETY: We are only ever constructed from an Obfuscated<T>,
 		// which guarantees us that the decrypted data is valid for the target type.
 		unsafe {
-			Data::from_raw(&self.data)
+			Data::from_raw(self.data.as_ref())
 		}
 	}
 }
 
-impl<T: ?Sized> Drop for Decrypted<T> {
+impl<T: ?Sized, A: SecureAlloc> Drop for Decrypted<T, A> {
 	fn drop(&mut self) {
-		for byte in &mut self.data {
+		for byte in self.data.as_mut() {
 			// Use write_volatile to ensure the zero-ing isn't optimized out.
 			// SAFETY: We know all the elements of the slice are valid.
 			unsafe {
 				core::ptr::write_volatile(byte, 0);
 			}
 		}
+		// If `self.data` is a `Buffer::Locked`, it is `munlock`ed and unmapped
+		// by its own `Drop` impl right after this function returns.
 	}
 }
 
-impl<T: ?Sized + Data> std::ops::Deref for Decrypted<T> {
+impl<T: ?Sized + Data, A: SecureAlloc> core::ops::Deref for Decrypted<T, A> {
 	type Target = T;
 
 	fn deref(&self) -> &Self::Target {
@@ -141,27 +393,27 @@ impl<T: ?Sized + Data> std::ops::Deref for Decrypted<T> {
 	}
 }
 
-impl<T: ?Sized + Data> AsRef<T> for Decrypted<T> {
+impl<T: ?Sized + Data, A: SecureAlloc> AsRef<T> for Decrypted<T, A> {
 	fn as_ref(&self) -> &T {
 		self.as_inner()
 	}
 }
 
-impl<T: ?Sized + Data> std::borrow::Borrow<T> for Decrypted<T> {
+impl<T: ?Sized + Data, A: SecureAlloc> core::borrow::Borrow<T> for Decrypted<T, A> {
 	fn borrow(&self) -> &T {
 		self.as_inner()
 	}
 }
 
-impl<T: ?Sized + Data + std::fmt::Display> std::fmt::Display for Decrypted<T> {
-	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		std::fmt::Display::fmt(self.as_inner(), f)
+impl<T: ?Sized + Data + core::fmt::Display, A: SecureAlloc> core::fmt::Display for Decrypted<T, A> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		core::fmt::Display::fmt(self.as_inner(), f)
 	}
 }
 
-impl<T: ?Sized + Data + std::fmt::Debug> std::fmt::Debug for Decrypted<T> {
-	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		std::fmt::Debug::fmt(self.as_inner(), f)
+impl<T: ?Sized + Data + core::fmt::Debug, A: SecureAlloc> core::fmt::Debug for Decrypted<T, A> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		core::fmt::Debug::fmt(self.as_inner(), f)
 	}
 }
 
@@ -0,0 +1,147 @@
+//! Platform support for locking decrypted buffers against swap and core dumps.
+//!
+//! Used by [`crate::Obfuscated::decrypt`] when the `mlock` feature is enabled.
+//! Allocation can fail (unsupported platform, syscall failure, `mlock` limits),
+//! in which case the caller is expected to fall back to a plain heap allocation.
+
+#[cfg(unix)]
+#[allow(non_camel_case_types)]
+mod ffi {
+	pub type c_int = i32;
+	pub type c_void = core::ffi::c_void;
+	pub type size_t = usize;
+
+	pub const PROT_READ: c_int = 0x1;
+	pub const PROT_WRITE: c_int = 0x2;
+	pub const MAP_PRIVATE: c_int = 0x0002;
+	#[cfg(not(target_os = "freebsd"))]
+	pub const MAP_ANONYMOUS: c_int = 0x0020;
+	#[cfg(target_os = "freebsd")]
+	pub const MAP_ANONYMOUS: c_int = 0x1000;
+
+	#[cfg(target_os = "linux")]
+	pub const MADV_DONTDUMP: c_int = 16;
+	#[cfg(target_os = "freebsd")]
+	pub const MADV_NOCORE: c_int = 8;
+
+	extern "C" {
+		pub fn mmap(addr: *mut c_void, len: size_t, prot: c_int, flags: c_int, fd: c_int, offset: isize) -> *mut c_void;
+		pub fn munmap(addr: *mut c_void, len: size_t) -> c_int;
+		pub fn mlock(addr: *const c_void, len: size_t) -> c_int;
+		pub fn munlock(addr: *const c_void, len: size_t) -> c_int;
+		#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+		pub fn madvise(addr: *mut c_void, len: size_t, advice: c_int) -> c_int;
+	}
+}
+
+/// A buffer backed by an anonymous, `mlock`ed memory mapping.
+///
+/// The mapping is marked to be excluded from core dumps where the platform supports it
+/// (`MADV_DONTDUMP` on Linux, `MADV_NOCORE` on FreeBSD), and is `munlock`ed and unmapped on drop.
+#[cfg(unix)]
+pub struct LockedBuffer {
+	ptr: core::ptr::NonNull<u8>,
+	len: usize,
+}
+
+// SAFETY: `LockedBuffer` exclusively owns the mapping it points to (nothing else ever gets a
+// copy of `ptr`), so it is sound to send it across threads or share `&LockedBuffer` between
+// them, exactly like a `Box<[u8]>` would be.
+#[cfg(unix)]
+unsafe impl Send for LockedBuffer {}
+#[cfg(unix)]
+unsafe impl Sync for LockedBuffer {}
+
+#[cfg(unix)]
+impl LockedBuffer {
+	/// Allocate and lock a buffer of `len` bytes.
+	///
+	/// Returns `None` if the platform refuses the mapping or the lock.
+	/// A refused core-dump advice is ignored: the buffer is still returned, locked.
+	pub fn new(len: usize) -> Option<Self> {
+		if len == 0 {
+			return None;
+		}
+
+		// SAFETY: we pass a null hint address and check the result for the MAP_FAILED sentinel.
+		let ptr = unsafe {
+			ffi::mmap(
+				core::ptr::null_mut(),
+				len,
+				ffi::PROT_READ | ffi::PROT_WRITE,
+				ffi::MAP_PRIVATE | ffi::MAP_ANONYMOUS,
+				-1,
+				0,
+			)
+		};
+		if ptr as isize == -1 {
+			return None;
+		}
+
+		// SAFETY: `ptr` is a valid mapping of at least `len` bytes, as returned by `mmap` above.
+		if unsafe { ffi::mlock(ptr, len) } != 0 {
+			// SAFETY: `ptr`/`len` are the exact mapping returned by `mmap` above.
+			unsafe {
+				ffi::munmap(ptr, len);
+			}
+			return None;
+		}
+
+		#[cfg(target_os = "linux")]
+		// SAFETY: `ptr`/`len` are the exact mapping returned by `mmap` above.
+		unsafe {
+			ffi::madvise(ptr, len, ffi::MADV_DONTDUMP);
+		}
+		#[cfg(target_os = "freebsd")]
+		// SAFETY: `ptr`/`len` are the exact mapping returned by `mmap` above.
+		unsafe {
+			ffi::madvise(ptr, len, ffi::MADV_NOCORE);
+		}
+
+		// SAFETY: `mmap` succeeded, so `ptr` is non-null.
+		let ptr = unsafe { core::ptr::NonNull::new_unchecked(ptr.cast::<u8>()) };
+		Some(Self { ptr, len })
+	}
+
+	pub fn as_slice(&self) -> &[u8] {
+		// SAFETY: `ptr` points to a mapping of at least `len` bytes that we own exclusively.
+		unsafe { core::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+	}
+
+	pub fn as_mut_slice(&mut self) -> &mut [u8] {
+		// SAFETY: `ptr` points to a mapping of at least `len` bytes that we own exclusively.
+		unsafe { core::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+	}
+}
+
+#[cfg(unix)]
+impl Drop for LockedBuffer {
+	fn drop(&mut self) {
+		let ptr = self.ptr.as_ptr().cast::<ffi::c_void>();
+		// SAFETY: `ptr`/`len` are the exact mapping that was mapped and locked in `new()`.
+		unsafe {
+			ffi::munlock(ptr, self.len);
+			ffi::munmap(ptr, self.len);
+		}
+	}
+}
+
+/// On non-Unix platforms there is no locking syscall to call, so allocation always fails
+/// and callers fall back to a plain heap allocation.
+#[cfg(not(unix))]
+pub struct LockedBuffer(core::convert::Infallible);
+
+#[cfg(not(unix))]
+impl LockedBuffer {
+	pub fn new(_len: usize) -> Option<Self> {
+		None
+	}
+
+	pub fn as_slice(&self) -> &[u8] {
+		match self.0 {}
+	}
+
+	pub fn as_mut_slice(&mut self) -> &mut [u8] {
+		match self.0 {}
+	}
+}
@@ -14,3 +14,73 @@ fn obfuscate_byte_str() {
 fn obfuscate_cstr() {
 	assert!(c"hello!" == obfustr::obfuscate!(c"hello!"));
 }
+
+#[test]
+fn obfuscate_ct_eq_str() {
+	assert!(obfustr::obfuscate_obj!("hello!") == "hello!");
+	assert!(obfustr::obfuscate_obj!("hello!") != "goodbye!");
+}
+
+#[test]
+fn obfuscate_ct_eq_byte_str() {
+	assert!(obfustr::obfuscate_obj!(b"hello!") == b"hello!".as_slice());
+	assert!(obfustr::obfuscate_obj!(b"hello!") != b"goodbye!".as_slice());
+}
+
+#[test]
+fn obfuscate_ct_eq_length_mismatch() {
+	assert!(obfustr::obfuscate_obj!("hello!") != "hello");
+	assert!(obfustr::obfuscate_obj!("hello!") != "hello!!");
+}
+
+#[test]
+fn encrypted_map() {
+	let secret = obfustr::encrypt!("hello!");
+	assert!(secret.map(|message| message == "hello!"));
+	assert!(secret.map(|message| message == "hello!"));
+}
+
+#[cfg(feature = "mlock")]
+#[test]
+fn heap_alloc_prefers_a_locked_buffer() {
+	use obfustr::{Buffer, SecureAlloc};
+
+	let buffer = obfustr::HeapAlloc::allocate(8);
+	assert!(matches!(buffer, Buffer::Locked(_)));
+}
+
+/// A [`SecureAlloc`](obfustr::SecureAlloc) backed by a fixed-size array instead of the heap,
+/// standing in for the "fixed static arena" use case the trait's docs call out.
+struct ArenaBuffer {
+	data: [u8; 32],
+	len: usize,
+}
+
+impl AsRef<[u8]> for ArenaBuffer {
+	fn as_ref(&self) -> &[u8] {
+		&self.data[..self.len]
+	}
+}
+
+impl AsMut<[u8]> for ArenaBuffer {
+	fn as_mut(&mut self) -> &mut [u8] {
+		&mut self.data[..self.len]
+	}
+}
+
+struct ArenaAlloc;
+
+impl obfustr::SecureAlloc for ArenaAlloc {
+	type Buffer = ArenaBuffer;
+
+	fn allocate(len: usize) -> Self::Buffer {
+		assert!(len <= 32);
+		ArenaBuffer { data: [0u8; 32], len }
+	}
+}
+
+#[test]
+fn decrypt_with_a_custom_secure_alloc() {
+	let decrypted = obfustr::obfuscate_obj!("hello!").decrypt::<ArenaAlloc>();
+	assert!(decrypted.as_ref() == "hello!");
+}